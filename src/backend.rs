@@ -0,0 +1,244 @@
+use crate::create_command;
+use std::error::Error;
+use std::process::Command;
+
+/// A version control system capable of cloning and updating a repository.
+///
+/// `fetch_repos` dispatches to whichever backend matches a [`crate::config::Repository`],
+/// either because it was picked explicitly in the TOML or because [`Backend::matches`]
+/// recognized the URL. To teach shepherd about another DVCS, implement this trait and add
+/// it to the list built by [`registry`].
+pub trait Backend {
+    /// Clone `url` into `dest`. When `recurse_submodules` is set, submodules (if any)
+    /// are cloned along with it.
+    fn clone(
+        &self,
+        url: &str,
+        dest: &str,
+        verbose: bool,
+        recurse_submodules: bool,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Pull new history into the checkout already present at `path`.
+    fn fetch(&self, path: &str, verbose: bool) -> Result<(), Box<dyn Error>>;
+
+    /// Returns true if `path` looks like a checkout managed by this backend.
+    fn is_repo(&self, path: &str) -> bool;
+
+    /// Returns true if `url` should be handled by this backend when no `backend` was set
+    /// explicitly. The fallback backend (git) always returns false here and is only
+    /// reached when nothing in the registry claims the URL.
+    fn matches(&self, url: &str) -> bool;
+
+    /// The name used to select this backend explicitly from the `backend` config field.
+    fn name(&self) -> &'static str;
+
+    /// Initializes and updates submodules at `path` after a clone or fetch, returning
+    /// the paths of any submodules that were newly initialized.
+    ///
+    /// Backends without a submodule concept get this no-op default.
+    fn sync_submodules(&self, _path: &str, _verbose: bool) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(vec![])
+    }
+}
+
+/// The default backend, shelling out to the system `git`.
+///
+/// Resolves `git` (or `git_path`, if set) to an absolute path via [`create_command`]
+/// rather than trusting `Command::new("git")` to find the right binary.
+pub struct Git {
+    git_path: Option<String>,
+}
+
+impl Git {
+    pub fn new(git_path: Option<String>) -> Git {
+        Git { git_path }
+    }
+
+    fn command(&self) -> Command {
+        create_command(self.git_path.as_deref().unwrap_or("git"))
+    }
+}
+
+impl Backend for Git {
+    fn clone(
+        &self,
+        url: &str,
+        dest: &str,
+        verbose: bool,
+        recurse_submodules: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut args = vec!["clone", url, dest];
+        if recurse_submodules {
+            args.push("--recurse-submodules");
+        }
+        crate::log!(verbose, "git {}", args.join(" "));
+        let status = self.command().args(&args).status()?;
+        require_success("git clone", status)
+    }
+
+    fn fetch(&self, path: &str, verbose: bool) -> Result<(), Box<dyn Error>> {
+        crate::log!(verbose, "git -C {} fetch --all", path);
+        let status = self
+            .command()
+            .args(["-C", path, "fetch", "--all"])
+            .status()?;
+        require_success("git fetch", status)
+    }
+
+    fn is_repo(&self, path: &str) -> bool {
+        std::path::Path::new(path).join(".git").exists()
+    }
+
+    fn matches(&self, _url: &str) -> bool {
+        // Git is the fallback backend, so it never claims a URL up front.
+        false
+    }
+
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn sync_submodules(&self, path: &str, verbose: bool) -> Result<Vec<String>, Box<dyn Error>> {
+        crate::log!(verbose, "git -C {} submodule sync --recursive", path);
+        let status = self
+            .command()
+            .args(["-C", path, "submodule", "sync", "--recursive"])
+            .status()?;
+        require_success("git submodule sync", status)?;
+
+        // Submodules not yet checked out show up prefixed with `-` in `status`; the ones
+        // listed here before `update --init` are what it initializes, so once the update
+        // succeeds this list is reported as the newly initialized submodules.
+        let before = self
+            .command()
+            .args(["-C", path, "submodule", "status", "--recursive"])
+            .output()?;
+        require_success("git submodule status", before.status)?;
+        let uninitialized: Vec<String> = String::from_utf8_lossy(&before.stdout)
+            .lines()
+            .filter(|line| line.starts_with('-'))
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(|name| name.to_string())
+            .collect();
+
+        crate::log!(
+            verbose,
+            "git -C {} submodule update --init --recursive",
+            path
+        );
+        let status = self
+            .command()
+            .args(["-C", path, "submodule", "update", "--init", "--recursive"])
+            .status()?;
+        require_success("git submodule update", status)?;
+
+        Ok(uninitialized)
+    }
+}
+
+/// A backend for Mercurial repositories, selected via the `hg::` URL prefix or an
+/// explicit `backend = "hg"` in the TOML.
+pub struct Mercurial;
+
+impl Backend for Mercurial {
+    fn clone(
+        &self,
+        url: &str,
+        dest: &str,
+        verbose: bool,
+        _recurse_submodules: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // Mercurial has no submodule equivalent, so the flag is ignored.
+        let url = strip_prefix(url);
+        crate::log!(verbose, "hg clone {} {}", url, dest);
+        let status = create_command("hg").args(["clone", url, dest]).status()?;
+        require_success("hg clone", status)
+    }
+
+    fn fetch(&self, path: &str, verbose: bool) -> Result<(), Box<dyn Error>> {
+        crate::log!(verbose, "hg -R {} pull", path);
+        let status = create_command("hg").args(["-R", path, "pull"]).status()?;
+        require_success("hg pull", status)
+    }
+
+    fn is_repo(&self, path: &str) -> bool {
+        std::path::Path::new(path).join(".hg").exists()
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        url.starts_with("hg::")
+    }
+
+    fn name(&self) -> &'static str {
+        "hg"
+    }
+}
+
+/// Turns a non-zero exit status into an `Err`.
+///
+/// `Command::status()`/`output()` only return `Err` when the process fails to spawn, not
+/// when it runs and exits non-zero (a bad URL, an auth failure, no network, …) — without
+/// this check those failures would be reported as successes in the fetch summary.
+fn require_success(cmd_desc: &str, status: std::process::ExitStatus) -> Result<(), Box<dyn Error>> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", cmd_desc, status).into())
+    }
+}
+
+/// Strips the `hg::` prefix used to mark Mercurial URLs, if present.
+fn strip_prefix(url: &str) -> &str {
+    url.strip_prefix("hg::").unwrap_or(url)
+}
+
+/// The known backends, in priority order. The last entry is the fallback reached when no
+/// backend claims the URL via [`Backend::matches`] and none was requested explicitly;
+/// adding a backend here (ahead of the fallback) is all `resolve` needs to pick it up.
+fn registry(git_path: Option<&str>) -> Vec<Box<dyn Backend>> {
+    vec![
+        Box::new(Mercurial),
+        Box::new(Git::new(git_path.map(str::to_string))),
+    ]
+}
+
+/// Picks the [`Backend`] to use for a repository and returns it alongside the URL with
+/// any backend-marker prefix (e.g. `hg::`) removed.
+///
+/// `explicit` comes from the repository's `backend` config field (matched against each
+/// candidate's [`Backend::name`]) and always wins; when it's absent, each candidate gets a
+/// chance to claim the URL via [`Backend::matches`], falling back to the registry's last
+/// entry. `git_path` is forwarded to [`Git`] so it respects `Config::git_path`.
+pub fn resolve<'a>(
+    explicit: Option<&str>,
+    url: &'a str,
+    git_path: Option<&str>,
+) -> (Box<dyn Backend>, &'a str) {
+    let mut candidates = registry(git_path);
+    let picked = match explicit {
+        Some(name) => {
+            let alias = name == "mercurial";
+            match candidates
+                .iter()
+                .position(|b| b.name() == name || (alias && b.name() == "hg"))
+            {
+                Some(i) => candidates.remove(i),
+                None => {
+                    eprintln!("Unknown backend `{}`, falling back to git", name);
+                    candidates.pop().expect("registry always has a fallback")
+                }
+            }
+        }
+        None => match candidates.iter().position(|b| b.matches(url)) {
+            Some(i) => candidates.remove(i),
+            None => candidates.pop().expect("registry always has a fallback"),
+        },
+    };
+    let url = if picked.name() == "hg" {
+        strip_prefix(url)
+    } else {
+        url
+    };
+    (picked, url)
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
 use std::fs;
@@ -10,23 +11,85 @@ pub struct Config {
     #[serde(skip)] // Skip serializing the configration file location
     config_location: String,
     pub source_dir: String,
+    /// How many repositories `fetch` clones/updates concurrently.
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    /// Default for [`Repository::recurse_submodules`] when a repo doesn't set its own.
+    #[serde(default)]
+    pub recurse_submodules: bool,
+    /// Overrides the resolved `git` binary (see [`crate::create_command`]), e.g. to pin
+    /// a specific install when several are on `PATH`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub git_path: Option<String>,
     pub repositories: Vec<Repository>,
+    /// Custom command shortcuts, e.g. `up = "fetch --all"`, read from an `[alias]`
+    /// table and expanded by [`crate::expand_aliases`] before dispatch.
+    ///
+    /// Declared after `repositories`: an empty `repositories` Vec serializes as a bare
+    /// `repositories = []`, and TOML doesn't allow a root-level `key = value` line after
+    /// a `[table]` header, so it has to come before `aliases`'s `[alias]` table.
+    #[serde(default, rename = "alias")]
+    pub aliases: HashMap<String, String>,
+}
+
+/// Default worker count for `fetch`'s thread pool when `jobs` is absent from the TOML.
+fn default_jobs() -> usize {
+    4
+}
+
+/// The `source_dir` default, following the same HOME-missing fallback as
+/// [`crate::default_config_path`] rather than panicking.
+fn default_source_dir() -> String {
+    match env::var("HOME") {
+        Ok(home) => format!("{}/sources", home),
+        Err(_) => "./sources".to_string(),
+    }
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 /// The internal representation of a single repository
 pub struct Repository {
     pub name: String,
     pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Which VCS backend manages this repository, e.g. `"git"` or `"hg"`.
+    ///
+    /// Left unset, the backend is inferred from the URL (see [`crate::backend::resolve`]).
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub backend: Option<String>,
+    /// Whether to clone/update submodules recursively. Unset falls back to
+    /// [`Config::recurse_submodules`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub recurse_submodules: Option<bool>,
 }
 
 impl Repository {
-    pub fn new(url: String) -> Repository {
+    pub fn new(name: String, url: String, category: Option<String>) -> Repository {
         Repository {
-            name: url.clone(),
+            name,
             url,
+            category,
+            backend: None,
+            recurse_submodules: None,
         }
     }
+
+    /// True if `other` identifies the same tracked repository.
+    ///
+    /// Compares only `name`/`url`/`category`: `backend`/`recurse_submodules` are per-repo
+    /// overrides set after the fact, not part of a repo's identity, so a derived
+    /// `PartialEq` would stop matching a freshly-`new`'d `Repository` (which always
+    /// leaves them `None`) against a tracked one that has them set.
+    pub fn is_same_repo(&self, other: &Repository) -> bool {
+        self.name == other.name && self.url == other.url && self.category == other.category
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
 }
 
 impl Config {
@@ -35,7 +98,11 @@ impl Config {
     /// The `source_dir` field defaults to `$HOME/sources`
     pub fn new() -> Config {
         Config {
-            source_dir: format!("{}/sources", env::var("HOME").unwrap()),
+            source_dir: default_source_dir(),
+            jobs: default_jobs(),
+            recurse_submodules: false,
+            git_path: None,
+            aliases: HashMap::new(),
             repositories: vec![],
             config_location: String::new(),
         }
@@ -44,7 +111,8 @@ impl Config {
     /// Read TOML file and load values into a Config struct
     ///
     /// If the filename doesn't exist, `read_config()` will write the current struct to the given
-    /// config file.
+    /// config file. Either way, `SHEPHERD_SOURCE_DIR` is applied afterwards, overriding
+    /// whatever `source_dir` ended up in the struct.
     pub fn read_config(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
         // Load raw yaml file. If the file doesn't exist, create it
         if std::path::Path::new(filename).exists() {
@@ -65,6 +133,9 @@ impl Config {
             fs::write(filename, config).expect("Couldn't write file");
         }
         self.config_location = filename.to_string();
+        if let Ok(source_dir) = env::var("SHEPHERD_SOURCE_DIR") {
+            self.source_dir = source_dir;
+        }
         Ok(())
     }
 
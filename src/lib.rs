@@ -1,10 +1,126 @@
+pub mod backend;
 pub mod config;
 use config::{Config, Repository};
 use std::env;
 use std::error::Error;
 use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
 
+/// Builds a [`Command`] for `program`, resolving it to an absolute path via a `PATH`
+/// lookup first.
+///
+/// `Command::new("git")` alone lets platforms that also search the current working
+/// directory (notably Windows) run a same-named binary sitting in an untrusted cwd
+/// instead of the real one on `PATH`. Resolving the path ourselves before constructing
+/// the `Command` closes that hole. If `program` already looks like a path (contains a
+/// separator), it's used as-is so explicit overrides like `Config::git_path` work. If no
+/// `PATH` entry holds `program`, the bare name is passed to `Command` unchanged.
+pub fn create_command(program: &str) -> Command {
+    let resolved = if program.contains(std::path::MAIN_SEPARATOR) {
+        PathBuf::from(program)
+    } else {
+        resolve_on_path(program).unwrap_or_else(|| PathBuf::from(program))
+    };
+    Command::new(resolved)
+}
+
+/// Searches `PATH` for `program`, skipping the current directory entirely.
+fn resolve_on_path(program: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find_map(|candidate| {
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            // On Windows the binary on disk carries an `.exe` the caller leaves off.
+            if cfg!(windows) {
+                let mut exe = candidate.into_os_string();
+                exe.push(".exe");
+                let exe = PathBuf::from(exe);
+                if exe.is_file() {
+                    return Some(exe);
+                }
+            }
+            None
+        })
+}
+
+/// Picks the default config file location.
+///
+/// `SHEPHERD_CONFIG` wins outright. Otherwise this follows XDG convention
+/// (`$XDG_CONFIG_HOME/shepherd/config.toml`, falling back to `$HOME/.config/...`), and
+/// falls back further to the current directory if `HOME` isn't set either, rather than
+/// panicking like a bare `env::var("HOME").unwrap()` would.
+fn default_config_path() -> String {
+    if let Ok(path) = env::var("SHEPHERD_CONFIG") {
+        return path;
+    }
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .or_else(|_| env::var("HOME").map(|home| format!("{}/.config", home)))
+        .unwrap_or_else(|_| ".".to_string());
+    format!("{}/shepherd/config.toml", config_home)
+}
+
+/// Scans the raw argv for an explicit `--config <path>`, otherwise falling back to
+/// `SHEPHERD_CONFIG`/XDG defaults via [`default_config_path`].
+///
+/// This has to run before [`State::new`] so the config file (and its `[alias]` table)
+/// can be loaded in time to expand aliases in the real argument list; see
+/// [`expand_aliases`].
+pub fn resolve_config_path(args: &[String]) -> String {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            if let Some(path) = iter.next() {
+                return path.clone();
+            }
+        }
+    }
+    default_config_path()
+}
+
+/// Expands a leading alias command from the `[alias]` TOML table into its full argument
+/// list, e.g. `up = "fetch --all"` turns `shepherd up` into `shepherd fetch --all`.
+///
+/// Only `args[1]` (the first argument after the program name) is checked; regular
+/// commands simply won't have a matching entry in `aliases`.
+pub fn expand_aliases(
+    args: Vec<String>,
+    aliases: &std::collections::HashMap<String, String>,
+) -> Vec<String> {
+    let expansion = match args.get(1).and_then(|cmd| aliases.get(cmd)) {
+        Some(expansion) => expansion,
+        None => return args,
+    };
+    let mut expanded: Vec<String> = vec![args[0].clone()];
+    expanded.extend(expansion.split_whitespace().map(String::from));
+    expanded.extend(args.into_iter().skip(2));
+    expanded
+}
+
+/// Prints a timestamped line to stderr when `verbose` is true; a no-op otherwise.
+///
+/// Used to trace exactly which backend command ran against which path without
+/// cluttering the normal status output.
+#[macro_export]
+macro_rules! log {
+    ($verbose:expr, $($arg:tt)*) => {
+        if $verbose {
+            let since_epoch = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap();
+            eprintln!(
+                "[{}.{:03}] {}",
+                since_epoch.as_secs(),
+                since_epoch.subsec_millis(),
+                format!($($arg)*)
+            );
+        }
+    };
+}
+
 #[derive(Debug)]
 /// Holds the current state of the application.
 ///
@@ -15,7 +131,14 @@ pub struct State {
     url: Option<String>,
     name: Option<String>,
     category: Option<String>,
-    pub config: String,
+    /// Repo or category names passed positionally to `fetch`, restricting it to those.
+    fetch_targets: Vec<String>,
+    /// Repo names passed to `fetch` via `-x`/`--exclude`, skipped even if otherwise selected.
+    fetch_exclude: Vec<String>,
+    /// Set by `fetch --all`, forcing every tracked repo regardless of `fetch_targets`.
+    fetch_all: bool,
+    /// Set by `-v`/`--verbose`, enabling timestamped [`log!`] output on stderr.
+    pub verbose: bool,
 }
 
 #[derive(Debug)]
@@ -41,30 +164,33 @@ impl State {
     ///
     /// The command argument is deterministic in the sense that it doesn't change once it's set. If
     /// no command has been given by the end of the parsing loop, it defaults to the help command
-    pub fn new(mut args: std::env::Args) -> State {
+    pub fn new(mut args: impl Iterator<Item = String>) -> State {
         let mut state = State {
             cmd: None,
             url: None,
             name: None,
             category: None,
-            config: format!("{}/.config/shepherd/config.toml", env::var("HOME").unwrap()),
+            fetch_targets: vec![],
+            fetch_exclude: vec![],
+            fetch_all: false,
+            verbose: false,
         };
 
         let mut arg = args.next();
         // Iterate through the command line arguments
-        while let Some(x) = &arg {
+        'parse: while let Some(x) = &arg {
             // Long arguments
             if x.starts_with("--") {
                 let option = x.strip_prefix("--").unwrap();
                 match option {
                     "help" => state.cmd = Some(Cmd::Help),
                     "dump-config" => state.cmd = Some(Cmd::DumpConfig),
-                    "config" => {
-                        let file = args.next();
-                        match file {
-                            Some(x) => state.config = x,
-                            None => eprintln!("Expected config argument"),
-                        }
+                    "verbose" => state.verbose = true,
+                    // The actual path is resolved from argv before `State::new` ever runs
+                    // (see `resolve_config_path`); just consume the value here so it isn't
+                    // mistaken for a command below.
+                    "config" if args.next().is_none() => {
+                        eprintln!("Expected config argument");
                     }
                     _ => {}
                 }
@@ -75,10 +201,11 @@ impl State {
                 for opt in options {
                     match opt {
                         'h' => state.cmd = Some(Cmd::Help),
+                        'v' => state.verbose = true,
                         _ => {}
                     }
                 }
-            } else if let None = state.cmd {
+            } else if state.cmd.is_none() {
                 // add command
                 if x == "add" {
                     let next = args.next();
@@ -112,21 +239,33 @@ impl State {
                 }
                 // fetch command
                 else if x == "fetch" {
-                    match state.cmd {
-                        None => {
-                            state.cmd = Some(Cmd::Fetch);
+                    if state.cmd.is_none() {
+                        state.cmd = Some(Cmd::Fetch);
+                        // Collect fetch-specific flags and positional targets until the
+                        // next unrecognized flag, mirroring how `add` consumes its args.
+                        let mut next = args.next();
+                        while let Some(n) = &next {
+                            if n == "-x" || n == "--exclude" {
+                                match args.next() {
+                                    Some(v) => state.fetch_exclude.push(v),
+                                    None => eprintln!("Expected value for --exclude\n"),
+                                }
+                            } else if n == "--all" {
+                                state.fetch_all = true;
+                            } else if n.starts_with('-') {
+                                break;
+                            } else {
+                                state.fetch_targets.push(n.clone());
+                            }
+                            next = args.next();
                         }
-                        _ => {}
+                        arg = next;
+                        continue 'parse;
                     }
                 }
                 // list command
-                else if x == "list" {
-                    match state.cmd {
-                        None => {
-                            state.cmd = Some(Cmd::List);
-                        }
-                        _ => {}
-                    }
+                else if x == "list" && state.cmd.is_none() {
+                    state.cmd = Some(Cmd::List);
                 }
             }
             arg = args.next();
@@ -155,20 +294,11 @@ pub fn run(state: State, mut config: Config) -> Result<(), Box<dyn Error>> {
         }
         Some(Cmd::Add) => {
             let repo = Repository::new(
-                match state.name {
-                    Some(x) => x,
-                    _ => String::new(),
-                },
-                match state.url {
-                    Some(x) => x,
-                    _ => String::new(),
-                },
-                match state.category {
-                    Some(x) => Some(x),
-                    _ => None,
-                },
+                state.name.unwrap_or_default(),
+                state.url.unwrap_or_default(),
+                state.category,
             );
-            if let None = config.repositories.iter().find(|x| **x == repo) {
+            if !config.repositories.iter().any(|x| x.is_same_repo(&repo)) {
                 config.repositories.push(repo);
                 config.save_config()?;
                 println!("Repository has been added");
@@ -216,7 +346,13 @@ pub fn run(state: State, mut config: Config) -> Result<(), Box<dyn Error>> {
             }
         }
         Some(Cmd::Fetch) => {
-            fetch_repos(config);
+            fetch_repos(
+                &config,
+                &state.fetch_targets,
+                &state.fetch_exclude,
+                state.fetch_all,
+                state.verbose,
+            );
         }
         Some(x) => {
             println!("{:?} hasn't been implemented yet!", x)
@@ -226,56 +362,160 @@ pub fn run(state: State, mut config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn fetch_repos(config: Config) {
-    // Make sure sources directory exists
-    match fs::create_dir_all(&config.source_dir) {
-        Err(e) => eprintln!("{}", e),
-        _ => {}
+#[derive(Debug)]
+/// The result of fetching or cloning a single repository, printed in the summary table.
+enum FetchStatus {
+    Updated,
+    Cloned,
+    Error(String),
+}
+
+/// Decides whether `repo` should be fetched given the `fetch` invocation's filters.
+///
+/// `--all` overrides `targets`. Otherwise an empty `targets` list selects every repo; a
+/// non-empty one restricts to repos whose name *or* category matches one of the
+/// targets, which is what lets `fetch <category>` work without a separate flag.
+/// `exclude` is applied last and removes repos by name regardless of the above.
+fn repo_selected(repo: &Repository, targets: &[String], exclude: &[String], all: bool) -> bool {
+    if exclude.iter().any(|x| x == &repo.name) {
+        return false;
     }
+    all || targets.is_empty()
+        || targets
+            .iter()
+            .any(|t| t == &repo.name || repo.category.as_deref() == Some(t.as_str()))
+}
 
-    // Loop through the repositories
-    for repo in config.repositories.iter() {
-        // Get the full path to the repository
-        let path: String = match &repo.category {
-            Some(x) => format!("{}/{}/", config.source_dir, x),
-            None => config.source_dir.clone(),
-        };
-        let fullpath: String = format!("{}/{}", path, repo.name);
-        // Check to see if the folder is already cloned, if so, just fetch
-        if std::path::Path::new(&fullpath).is_dir() {
-            println!("=== FETCHING {} ===", repo.name);
-            Command::new("git")
-                .args(["-C", &fullpath, "fetch", "--all"])
-                .status()
-                .unwrap();
-        } else {
-            println!("=== {} doesn't exist locally ===", repo.name);
-            println!("=== CLONING {} ===", repo.name);
-            match fs::create_dir_all(&path) {
-                Err(e) => eprintln!("{}", e),
-                _ => {}
+/// Clones or fetches a single repository and reports what happened.
+fn fetch_one(
+    repo: &Repository,
+    source_dir: &str,
+    verbose: bool,
+    recurse_submodules: bool,
+    git_path: Option<&str>,
+) -> FetchStatus {
+    let path: String = match &repo.category {
+        Some(x) => format!("{}/{}/", source_dir, x),
+        None => source_dir.to_string(),
+    };
+    let fullpath: String = format!("{}/{}", path, repo.name);
+    let (vcs, url) = backend::resolve(repo.backend.as_deref(), &repo.url, git_path);
+    log!(verbose, "{} backend selected for {}", vcs.name(), repo.name);
+
+    let status = if vcs.is_repo(&fullpath) {
+        println!("=== FETCHING {} ===", repo.name);
+        match vcs.fetch(&fullpath, verbose) {
+            Ok(()) => FetchStatus::Updated,
+            Err(e) => return FetchStatus::Error(e.to_string()),
+        }
+    } else {
+        println!("=== CLONING {} ===", repo.name);
+        if let Err(e) = fs::create_dir_all(&path) {
+            return FetchStatus::Error(e.to_string());
+        }
+        match vcs.clone(url, &fullpath, verbose, recurse_submodules) {
+            Ok(()) => FetchStatus::Cloned,
+            Err(e) => return FetchStatus::Error(e.to_string()),
+        }
+    };
+
+    if recurse_submodules {
+        match vcs.sync_submodules(&fullpath, verbose) {
+            Ok(new) if !new.is_empty() => {
+                println!(
+                    "=== {} initialized new submodules: {} ===",
+                    repo.name,
+                    new.join(", ")
+                );
             }
-            println!("{}", path);
-            Command::new("git")
-                .args(["-C", &path, "clone", &repo.url, &repo.name])
-                .status()
-                .unwrap();
+            Ok(_) => {}
+            Err(e) => eprintln!("{}: failed to sync submodules: {}", repo.name, e),
         }
     }
+
+    status
+}
+
+fn fetch_repos(config: &Config, targets: &[String], exclude: &[String], all: bool, verbose: bool) {
+    // Make sure sources directory exists
+    if let Err(e) = fs::create_dir_all(&config.source_dir) {
+        eprintln!("{}", e)
+    }
+
+    let repos: std::collections::VecDeque<&Repository> = config
+        .repositories
+        .iter()
+        .filter(|repo| repo_selected(repo, targets, exclude, all))
+        .collect();
+
+    let queue = std::sync::Mutex::new(repos);
+    let results: std::sync::Mutex<Vec<(&str, FetchStatus)>> = std::sync::Mutex::new(vec![]);
+    let jobs = config.jobs.max(1);
+    let default_recurse_submodules = config.recurse_submodules;
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            let queue = &queue;
+            let results = &results;
+            let source_dir = config.source_dir.as_str();
+            let git_path = config.git_path.as_deref();
+            scope.spawn(move || loop {
+                let repo = match queue.lock().unwrap().pop_front() {
+                    Some(repo) => repo,
+                    None => break,
+                };
+                let recurse_submodules = repo
+                    .recurse_submodules
+                    .unwrap_or(default_recurse_submodules);
+                let status = fetch_one(repo, source_dir, verbose, recurse_submodules, git_path);
+                results.lock().unwrap().push((repo.name.as_str(), status));
+            });
+        }
+    });
+
+    print_summary(results.into_inner().unwrap());
+}
+
+/// Prints a table of which repos were updated, cloned, or errored after a `fetch` run.
+fn print_summary(mut results: Vec<(&str, FetchStatus)>) {
+    results.sort_by_key(|(name, _)| *name);
+    let name_width = results
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    println!();
+    println!("{:name$}  STATUS", "REPO", name = name_width);
+    for (name, status) in &results {
+        let status = match status {
+            FetchStatus::Updated => "updated".to_string(),
+            FetchStatus::Cloned => "cloned".to_string(),
+            FetchStatus::Error(e) => format!("error: {}", e),
+        };
+        println!("{:name$}  {}", name, status, name = name_width);
+    }
 }
 
 fn help_msg() -> String {
-    format!(
-        "Git repository manager
+    "Git repository manager
 
 USAGE:
     shepherd [--help] <command> [<args>]
+    shepherd fetch [<name-or-category>...] [-x|--exclude <name>]... [--all]
 
 OPTIONS:
     -h, --help      Print out this help message
     --config        Specify the location of the configuration file
     --dump-config   Dump the current configuration
     -c, --category  Specify the category when adding a repository
+    -x, --exclude   Skip a repo when fetching (repeatable, fetch only)
+    --all           Fetch every tracked repo, ignoring name/category filters
+                    (--exclude still applies)
+    -v, --verbose   Print timestamped details of every backend command run
+
+ENVIRONMENT:
+    SHEPHERD_CONFIG       Override the configuration file location
+    SHEPHERD_SOURCE_DIR   Override the configured source_dir
 
 COMMANDS:
 General
@@ -283,7 +523,165 @@ General
 
 Manage Repositories
     add     Add another git repo to keep track of
-    fetch   Update currently tracked repos
-    list    list out the currently tracked repos"
-    )
+    fetch   Update currently tracked repos, optionally restricted to the
+            given repo or category names
+    list    list out the currently tracked repos
+
+Commands defined in the configuration file's [alias] table are expanded
+before dispatch, e.g. `up = \"fetch --all\"` lets `shepherd up` run `shepherd
+fetch --all`."
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // Serializes the tests below that mutate process-wide env vars (PATH, HOME,
+    // XDG_CONFIG_HOME, SHEPHERD_CONFIG), since `cargo test` runs tests concurrently in
+    // one process.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn repo_selected_all_overrides_targets() {
+        let repo = Repository::new("foo".to_string(), "url".to_string(), None);
+        assert!(repo_selected(&repo, &["bar".to_string()], &[], true));
+    }
+
+    #[test]
+    fn repo_selected_empty_targets_selects_everything() {
+        let repo = Repository::new("foo".to_string(), "url".to_string(), None);
+        assert!(repo_selected(&repo, &[], &[], false));
+    }
+
+    #[test]
+    fn repo_selected_targets_match_name_or_category() {
+        let repo = Repository::new(
+            "foo".to_string(),
+            "url".to_string(),
+            Some("infra".to_string()),
+        );
+        assert!(repo_selected(&repo, &["foo".to_string()], &[], false));
+        assert!(repo_selected(&repo, &["infra".to_string()], &[], false));
+        assert!(!repo_selected(&repo, &["other".to_string()], &[], false));
+    }
+
+    #[test]
+    fn repo_selected_exclude_wins_even_over_all() {
+        let repo = Repository::new("foo".to_string(), "url".to_string(), None);
+        assert!(!repo_selected(&repo, &[], &["foo".to_string()], true));
+    }
+
+    #[test]
+    fn expand_aliases_expands_the_first_argument() {
+        let mut aliases = HashMap::new();
+        aliases.insert("up".to_string(), "fetch --all".to_string());
+        let args = vec!["shepherd".to_string(), "up".to_string()];
+        assert_eq!(
+            expand_aliases(args, &aliases),
+            vec!["shepherd", "fetch", "--all"]
+        );
+    }
+
+    #[test]
+    fn expand_aliases_preserves_trailing_args() {
+        let mut aliases = HashMap::new();
+        aliases.insert("up".to_string(), "fetch".to_string());
+        let args = vec![
+            "shepherd".to_string(),
+            "up".to_string(),
+            "infra".to_string(),
+        ];
+        assert_eq!(
+            expand_aliases(args, &aliases),
+            vec!["shepherd", "fetch", "infra"]
+        );
+    }
+
+    #[test]
+    fn expand_aliases_only_looks_at_args_1() {
+        // A flag ahead of the alias (`shepherd -v up`) shifts `up` into args[2], so it's
+        // never looked up and passes through unexpanded. Pinning this down because it's
+        // a sharp edge, not because it's necessarily the desired behavior.
+        let mut aliases = HashMap::new();
+        aliases.insert("up".to_string(), "fetch --all".to_string());
+        let args = vec!["shepherd".to_string(), "-v".to_string(), "up".to_string()];
+        assert_eq!(expand_aliases(args.clone(), &aliases), args);
+    }
+
+    #[test]
+    fn expand_aliases_no_match_passes_through_unchanged() {
+        let aliases = HashMap::new();
+        let args = vec!["shepherd".to_string(), "list".to_string()];
+        assert_eq!(expand_aliases(args.clone(), &aliases), args);
+    }
+
+    #[test]
+    fn default_config_path_honors_shepherd_config_override() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let prev = env::var("SHEPHERD_CONFIG").ok();
+        env::set_var("SHEPHERD_CONFIG", "/tmp/custom-shepherd.toml");
+        assert_eq!(default_config_path(), "/tmp/custom-shepherd.toml");
+        match prev {
+            Some(v) => env::set_var("SHEPHERD_CONFIG", v),
+            None => env::remove_var("SHEPHERD_CONFIG"),
+        }
+    }
+
+    #[test]
+    fn default_config_path_falls_back_from_xdg_to_home() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let prev_shepherd = env::var("SHEPHERD_CONFIG").ok();
+        let prev_xdg = env::var("XDG_CONFIG_HOME").ok();
+        let prev_home = env::var("HOME").ok();
+
+        env::remove_var("SHEPHERD_CONFIG");
+        env::remove_var("XDG_CONFIG_HOME");
+        env::set_var("HOME", "/home/tester");
+        assert_eq!(
+            default_config_path(),
+            "/home/tester/.config/shepherd/config.toml"
+        );
+
+        env::set_var("XDG_CONFIG_HOME", "/xdg");
+        assert_eq!(default_config_path(), "/xdg/shepherd/config.toml");
+
+        match prev_shepherd {
+            Some(v) => env::set_var("SHEPHERD_CONFIG", v),
+            None => env::remove_var("SHEPHERD_CONFIG"),
+        }
+        match prev_xdg {
+            Some(v) => env::set_var("XDG_CONFIG_HOME", v),
+            None => env::remove_var("XDG_CONFIG_HOME"),
+        }
+        match prev_home {
+            Some(v) => env::set_var("HOME", v),
+            None => env::remove_var("HOME"),
+        }
+    }
+
+    #[test]
+    fn resolve_on_path_finds_an_executable_and_skips_missing_ones() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let dir = std::env::temp_dir().join(format!("shepherd-test-path-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bin = dir.join("totally-fake-shepherd-binary");
+        fs::write(&bin, b"").unwrap();
+
+        let prev_path = env::var_os("PATH");
+        env::set_var("PATH", &dir);
+        assert_eq!(
+            resolve_on_path("totally-fake-shepherd-binary"),
+            Some(bin.clone())
+        );
+        assert_eq!(resolve_on_path("no-such-binary-anywhere"), None);
+
+        match prev_path {
+            Some(v) => env::set_var("PATH", v),
+            None => env::remove_var("PATH"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,15 +1,21 @@
-use std::env;
-use shepherd::State;
 use shepherd::config::Config;
+use shepherd::State;
+use std::env;
 
 fn main() {
-    let args = env::args();
-    let state = State::new(args);
+    let args: Vec<String> = env::args().collect();
+
+    // The config file has to be read before full argument parsing so its `[alias]`
+    // table is available to expand aliases in the real argument list below.
+    let config_path = shepherd::resolve_config_path(&args);
     let mut config = Config::new();
-    if let Err(e) = config.read_config(&state.config) {
+    if let Err(e) = config.read_config(&config_path) {
         eprintln!("Configuration Error: {}", e);
         std::process::exit(1);
     }
+
+    let args = shepherd::expand_aliases(args, &config.aliases);
+    let state = State::new(args.into_iter());
     if let Err(e) = shepherd::run(state, config) {
         eprintln!("{}", e);
     }